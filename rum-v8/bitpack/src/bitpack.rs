@@ -59,7 +59,7 @@ pub fn newu(word: u64, width: u64, lsb: u64, value: u64) -> Option<u64> {
         panic!("You cannot retrieve that value from the bit field and/or word of that length");
     }else{
         if fitsu(value,width){
-            let left = (word >> (lsb+width)) <<(lsb+width); // clears everything on right
+            let left = shl(shr(word, lsb+width), lsb+width); // clears everything on right
             let right = shr(shl(word,word_width as u64 - lsb),word_width as u64 - lsb); //clears everything on left
             let val = value <<lsb; //moves value to position of lsb with trailing 0s to the right
             return Some(left | right as u64 | val);
@@ -70,13 +70,16 @@ pub fn newu(word: u64, width: u64, lsb: u64, value: u64) -> Option<u64> {
 }
 
 // a reasonable approach for shifting by 64 or more would be to set the result to be all zeroes/all ones?
+// shifting a u64 by 64 bits is itself an overflow (panics in debug, UB to rely on in
+// release), so treat "shift every bit out" as 0 rather than forwarding the shift
+// amount straight to `<<`/`>>`. `newu` hits this whenever `lsb` is 0.
 #[inline]
 pub fn shl(word: u64, shift: u64)->u64{
-        return word << shift;
+        if shift >= 64 { 0 } else { word << shift }
 }
 #[inline]
 pub fn shr(word:u64,shift:u64)->u64{
-        return word >> shift;
+        if shift >= 64 { 0 } else { word >> shift }
 }
 
 /// Return a modified version of the unsigned `word`,
@@ -0,0 +1,210 @@
+use bitpack::bitpack::newu;
+use std::fmt;
+use std::io::Write;
+
+// Everything that can go wrong turning a line of assembly into a UM word.
+#[derive(Debug)]
+pub enum AsmError {
+	UnknownMnemonic(String),
+	WrongArgCount { mnemonic: String, expected: usize, found: usize },
+	BadRegister(String),
+	BadImmediate(String),
+	ImmediateOutOfRange(u64),
+}
+
+impl fmt::Display for AsmError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			AsmError::UnknownMnemonic(m) => write!(f, "unknown mnemonic: {}", m),
+			AsmError::WrongArgCount { mnemonic, expected, found } => write!(
+				f,
+				"{} expects {} operand(s), found {}",
+				mnemonic, expected, found
+			),
+			AsmError::BadRegister(r) => write!(f, "not a register: {}", r),
+			AsmError::BadImmediate(v) => write!(f, "not a valid immediate: {}", v),
+			AsmError::ImmediateOutOfRange(v) => write!(f, "immediate {} does not fit in 25 bits", v),
+		}
+	}
+}
+
+impl std::error::Error for AsmError {}
+
+// Parses "r0".."r7" into a register index.
+fn register(tok: &str) -> Result<u64, AsmError> {
+	let digit = tok.strip_prefix('r').ok_or_else(|| AsmError::BadRegister(tok.to_string()))?;
+	let n: u64 = digit.parse().map_err(|_| AsmError::BadRegister(tok.to_string()))?;
+	if n < 8 {
+		Ok(n)
+	} else {
+		Err(AsmError::BadRegister(tok.to_string()))
+	}
+}
+
+fn immediate(tok: &str) -> Result<u64, AsmError> {
+	tok.parse().map_err(|_| AsmError::BadImmediate(tok.to_string()))
+}
+
+fn expect_args<'a>(mnemonic: &str, args: &'a [&str], count: usize) -> Result<&'a [&'a str], AsmError> {
+	if args.len() == count {
+		Ok(args)
+	} else {
+		Err(AsmError::WrongArgCount {
+			mnemonic: mnemonic.to_string(),
+			expected: count,
+			found: args.len(),
+		})
+	}
+}
+
+// Packs a three-register op: opcode at bits 28-31, A at 6-8, B at 3-5, C at 0-2.
+fn three_reg(opcode: u64, args: &[&str]) -> Result<u32, AsmError> {
+	let a = register(args[0])?;
+	let b = register(args[1])?;
+	let c = register(args[2])?;
+	let word = newu(0, 4, 28, opcode).unwrap();
+	let word = newu(word, 3, 6, a).unwrap();
+	let word = newu(word, 3, 3, b).unwrap();
+	let word = newu(word, 3, 0, c).unwrap();
+	Ok(word as u32)
+}
+
+// Packs a two-register op (B and C only, A left as 0).
+fn two_reg(opcode: u64, args: &[&str]) -> Result<u32, AsmError> {
+	let b = register(args[0])?;
+	let c = register(args[1])?;
+	let word = newu(0, 4, 28, opcode).unwrap();
+	let word = newu(word, 3, 3, b).unwrap();
+	let word = newu(word, 3, 0, c).unwrap();
+	Ok(word as u32)
+}
+
+// Packs a one-register op (C only).
+fn one_reg(opcode: u64, args: &[&str]) -> Result<u32, AsmError> {
+	let c = register(args[0])?;
+	let word = newu(0, 4, 28, opcode).unwrap();
+	let word = newu(word, 3, 0, c).unwrap();
+	Ok(word as u32)
+}
+
+// Assembles one line of the form `mnemonic arg arg ...` into a UM word.
+fn assemble_line(mnemonic: &str, args: &[&str]) -> Result<u32, AsmError> {
+	match mnemonic {
+		"cmov" => three_reg(0, expect_args(mnemonic, args, 3)?),
+		"load" => three_reg(1, expect_args(mnemonic, args, 3)?),
+		"store" => three_reg(2, expect_args(mnemonic, args, 3)?),
+		"add" => three_reg(3, expect_args(mnemonic, args, 3)?),
+		"mul" => three_reg(4, expect_args(mnemonic, args, 3)?),
+		"div" => three_reg(5, expect_args(mnemonic, args, 3)?),
+		"nand" => three_reg(6, expect_args(mnemonic, args, 3)?),
+		"halt" => {
+			expect_args(mnemonic, args, 0)?;
+			Ok(newu(0, 4, 28, 7).unwrap() as u32)
+		}
+		"map" => two_reg(8, expect_args(mnemonic, args, 2)?),
+		"unmap" => one_reg(9, expect_args(mnemonic, args, 1)?),
+		"output" => one_reg(10, expect_args(mnemonic, args, 1)?),
+		"input" => one_reg(11, expect_args(mnemonic, args, 1)?),
+		"loadprog" => two_reg(12, expect_args(mnemonic, args, 2)?),
+		"loadval" => {
+			let args = expect_args(mnemonic, args, 2)?;
+			let a = register(args[0])?;
+			let value = immediate(args[1])?;
+			let word = newu(0, 4, 28, 13).unwrap();
+			let word = newu(word, 3, 25, a).unwrap();
+			let word = newu(word, 25, 0, value).ok_or(AsmError::ImmediateOutOfRange(value))?;
+			Ok(word as u32)
+		}
+		_ => Err(AsmError::UnknownMnemonic(mnemonic.to_string())),
+	}
+}
+
+// Assembles a small line-oriented UM assembly (one instruction per line, blank
+// lines and `#`-comments ignored) into the big-endian words `boot`/`run` expect,
+// so a program can round-trip through `assemble` -> `Vm::boot`/`run` -> `disassemble`.
+pub fn assemble(src: &str) -> Result<Vec<u32>, AsmError> {
+	let mut program = Vec::new();
+	for line in src.lines() {
+		let line = match line.find('#') {
+			Some(idx) => &line[..idx],
+			None => line,
+		};
+		let tokens: Vec<&str> = line.split_whitespace().collect();
+		let (mnemonic, args) = match tokens.split_first() {
+			Some((m, rest)) => (*m, rest),
+			None => continue,
+		};
+		program.push(assemble_line(mnemonic, args)?);
+	}
+	Ok(program)
+}
+
+// Serializes an assembled program as big-endian `u32` words, matching the format
+// `boot` reads back in.
+pub fn write_program<W: Write>(prog: &[u32], out: &mut W) -> std::io::Result<()> {
+	for &word in prog {
+		out.write_all(&word.to_be_bytes())?;
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::disasm::{self, Instruction};
+
+	#[test]
+	fn assembles_three_reg_op() {
+		let program = assemble("add r1 r2 r3\n").unwrap();
+		assert_eq!(disasm::decode(program[0]).unwrap(), Instruction::Add { a: 1, b: 2, c: 3 });
+	}
+
+	#[test]
+	fn assembles_load_val() {
+		let program = assemble("loadval r4 12345\n").unwrap();
+		assert_eq!(disasm::decode(program[0]).unwrap(), Instruction::LoadVal { a: 4, val: 12345 });
+	}
+
+	#[test]
+	fn ignores_blank_lines_and_comments() {
+		let program = assemble("# a comment\n\nhalt # trailing comment\n").unwrap();
+		assert_eq!(program.len(), 1);
+		assert_eq!(disasm::decode(program[0]).unwrap(), Instruction::Halt);
+	}
+
+	#[test]
+	fn round_trips_a_whole_program_through_disassemble() {
+		let src = "loadval r0 72\noutput r0\nhalt\n";
+		let program = assemble(src).unwrap();
+		let insts: Vec<Instruction> = disasm::disassemble(&program).map(|(_, i)| i.unwrap()).collect();
+		assert_eq!(
+			insts,
+			vec![
+				Instruction::LoadVal { a: 0, val: 72 },
+				Instruction::Output { c: 0 },
+				Instruction::Halt,
+			]
+		);
+	}
+
+	#[test]
+	fn rejects_unknown_mnemonic() {
+		assert!(matches!(assemble("frobnicate r0\n"), Err(AsmError::UnknownMnemonic(_))));
+	}
+
+	#[test]
+	fn rejects_wrong_arg_count() {
+		assert!(matches!(
+			assemble("add r1 r2\n"),
+			Err(AsmError::WrongArgCount { expected: 3, found: 2, .. })
+		));
+	}
+
+	#[test]
+	fn rejects_out_of_range_immediate() {
+		assert!(matches!(
+			assemble("loadval r0 99999999999\n"),
+			Err(AsmError::ImmediateOutOfRange(_))
+		));
+	}
+}
@@ -0,0 +1,91 @@
+// A growable bitset over `Vec<u32>` words, one bit per segment id: bit `i` means
+// segment `i` is currently mapped. This gives `seg_load`/`seg_store`/`unmap_seg` an
+// O(1) liveness check instead of relying on a zero-length `Vec` in `memory` (which a
+// stale segment id could still index into) or scanning `unmapped_segs`.
+pub struct SegBitset {
+	words: Vec<u32>,
+}
+
+// Number of u32 words needed to hold `n` bits: `n / 32`, plus one unless `n % 32
+// == 0`. Unlike `(n + 31) / 32`, this never adds to `n`, so it cannot overflow
+// for `n` near `usize::MAX`.
+fn blocks_for_bits(n: usize) -> usize {
+	if n % 32 == 0 {
+		n / 32
+	} else {
+		n / 32 + 1
+	}
+}
+
+impl SegBitset {
+	pub fn new() -> Self {
+		SegBitset { words: Vec::new() }
+	}
+
+	fn ensure_capacity(&mut self, bits: usize) {
+		let blocks = blocks_for_bits(bits);
+		if blocks > self.words.len() {
+			self.words.resize(blocks, 0);
+		}
+	}
+
+	pub fn set(&mut self, i: usize) {
+		self.ensure_capacity(i + 1);
+		self.words[i / 32] |= 1 << (i % 32);
+	}
+
+	pub fn clear(&mut self, i: usize) {
+		if let Some(word) = self.words.get_mut(i / 32) {
+			*word &= !(1 << (i % 32));
+		}
+	}
+
+	pub fn get(&self, i: usize) -> bool {
+		self.words.get(i / 32).map_or(false, |word| word & (1 << (i % 32)) != 0)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn unset_bit_defaults_false() {
+		let bits = SegBitset::new();
+		assert!(!bits.get(0));
+		assert!(!bits.get(100));
+	}
+
+	#[test]
+	fn set_then_clear_round_trips() {
+		let mut bits = SegBitset::new();
+		bits.set(5);
+		assert!(bits.get(5));
+		assert!(!bits.get(4));
+		bits.clear(5);
+		assert!(!bits.get(5));
+	}
+
+	#[test]
+	fn clear_on_an_id_never_set_is_a_no_op() {
+		let mut bits = SegBitset::new();
+		bits.clear(3);
+		assert!(!bits.get(3));
+	}
+
+	#[test]
+	fn grows_to_fit_ids_past_the_first_word() {
+		let mut bits = SegBitset::new();
+		bits.set(40);
+		assert!(bits.get(40));
+		assert!(!bits.get(39));
+		assert!(!bits.get(41));
+	}
+
+	#[test]
+	fn blocks_for_bits_rounds_up_except_on_word_boundary() {
+		assert_eq!(blocks_for_bits(0), 0);
+		assert_eq!(blocks_for_bits(32), 1);
+		assert_eq!(blocks_for_bits(33), 2);
+	}
+}
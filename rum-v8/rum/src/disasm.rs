@@ -0,0 +1,130 @@
+use crate::instructs::{get, RA, RB, RC, RL, VL};
+use crate::rum::VmError;
+use std::fmt;
+
+// A decoded UM instruction, with its fields already pulled out of the word, for
+// readable disassembly. `Vm::execute` dispatches on the packed
+// `instructs::DecodedInst` instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+	CondMove { a: usize, b: usize, c: usize },
+	SegLoad { a: usize, b: usize, c: usize },
+	SegStore { a: usize, b: usize, c: usize },
+	Add { a: usize, b: usize, c: usize },
+	Mul { a: usize, b: usize, c: usize },
+	Div { a: usize, b: usize, c: usize },
+	Nand { a: usize, b: usize, c: usize },
+	Halt,
+	MapSeg { b: usize, c: usize },
+	UnmapSeg { c: usize },
+	Output { c: usize },
+	Input { c: usize },
+	LoadProg { b: usize, c: usize },
+	LoadVal { a: usize, val: u32 },
+}
+
+// Decodes a raw UM word into an `Instruction`, reusing the same `Field`/`get`
+// machinery `instructs.rs` uses to dispatch, so the bit layout only lives in one place.
+pub fn decode(word: u32) -> Result<Instruction, VmError> {
+	let opcode = (word >> 28) & (1 << 4) - 1;
+	let a = get(&RA, word);
+	let b = get(&RB, word);
+	let c = get(&RC, word);
+
+	match opcode {
+		0 => Ok(Instruction::CondMove { a, b, c }),
+		1 => Ok(Instruction::SegLoad { a, b, c }),
+		2 => Ok(Instruction::SegStore { a, b, c }),
+		3 => Ok(Instruction::Add { a, b, c }),
+		4 => Ok(Instruction::Mul { a, b, c }),
+		5 => Ok(Instruction::Div { a, b, c }),
+		6 => Ok(Instruction::Nand { a, b, c }),
+		7 => Ok(Instruction::Halt),
+		8 => Ok(Instruction::MapSeg { b, c }),
+		9 => Ok(Instruction::UnmapSeg { c }),
+		10 => Ok(Instruction::Output { c }),
+		11 => Ok(Instruction::Input { c }),
+		12 => Ok(Instruction::LoadProg { b, c }),
+		13 => Ok(Instruction::LoadVal {
+			a: get(&RL, word),
+			val: get(&VL, word) as u32,
+		}),
+		_ => Err(VmError::InvalidOpcode(opcode)),
+	}
+}
+
+impl fmt::Display for Instruction {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Instruction::CondMove { a, b, c } => write!(f, "if r{} != 0 then r{} := r{}", c, a, b),
+			Instruction::SegLoad { a, b, c } => write!(f, "load r{} := seg[r{}][r{}]", a, b, c),
+			Instruction::SegStore { a, b, c } => write!(f, "store seg[r{}][r{}] := r{}", a, b, c),
+			Instruction::Add { a, b, c } => write!(f, "r{} := r{} + r{}", a, b, c),
+			Instruction::Mul { a, b, c } => write!(f, "r{} := r{} * r{}", a, b, c),
+			Instruction::Div { a, b, c } => write!(f, "r{} := r{} / r{}", a, b, c),
+			Instruction::Nand { a, b, c } => write!(f, "r{} := !(r{} & r{})", a, b, c),
+			Instruction::Halt => write!(f, "halt"),
+			Instruction::MapSeg { b, c } => write!(f, "r{} := map(r{})", b, c),
+			Instruction::UnmapSeg { c } => write!(f, "unmap r{}", c),
+			Instruction::Output { c } => write!(f, "output r{}", c),
+			Instruction::Input { c } => write!(f, "r{} := input", c),
+			Instruction::LoadProg { b, c } => write!(f, "load-prog r{}; goto r{}", b, c),
+			Instruction::LoadVal { a, val } => write!(f, "load-val r{} := {}", a, val),
+		}
+	}
+}
+
+// Decodes an entire program, pairing each word with its offset so a caller can print
+// a `rumdump`-style listing or diff two programs instruction-by-instruction.
+pub fn disassemble(program: &[u32]) -> impl Iterator<Item = (u32, Result<Instruction, VmError>)> + '_ {
+	program
+		.iter()
+		.enumerate()
+		.map(|(offset, &word)| (offset as u32, decode(word)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn decode_three_reg() {
+		// opcode 3 (add), a=1, b=2, c=3
+		let word = (3 << 28) | (1 << 6) | (2 << 3) | 3;
+		assert_eq!(decode(word).unwrap(), Instruction::Add { a: 1, b: 2, c: 3 });
+	}
+
+	#[test]
+	fn decode_halt_ignores_register_bits() {
+		let word = 7 << 28;
+		assert_eq!(decode(word).unwrap(), Instruction::Halt);
+	}
+
+	#[test]
+	fn decode_load_val_splits_register_and_immediate() {
+		// opcode 13, a=5 at bits 25-27, immediate 1234 in the low 25 bits
+		let word = (13 << 28) | (5 << 25) | 1234;
+		assert_eq!(decode(word).unwrap(), Instruction::LoadVal { a: 5, val: 1234 });
+	}
+
+	#[test]
+	fn decode_rejects_unassigned_opcode() {
+		let word = 14 << 28;
+		assert!(matches!(decode(word), Err(VmError::InvalidOpcode(14))));
+	}
+
+	#[test]
+	fn disassemble_pairs_offsets_with_decoded_instructions() {
+		let program = [7 << 28, (13 << 28) | (0 << 25) | 5];
+		let out: Vec<(u32, Instruction)> = disassemble(&program)
+			.map(|(offset, inst)| (offset, inst.unwrap()))
+			.collect();
+		assert_eq!(out, vec![(0, Instruction::Halt), (1, Instruction::LoadVal { a: 0, val: 5 })]);
+	}
+
+	#[test]
+	fn display_matches_mnemonic_style() {
+		assert_eq!(Instruction::Add { a: 1, b: 2, c: 3 }.to_string(), "r1 := r2 + r3");
+		assert_eq!(Instruction::LoadVal { a: 0, val: 5 }.to_string(), "load-val r0 := 5");
+	}
+}
@@ -1,4 +1,4 @@
-use crate::rum::Vm;
+use crate::rum::{Vm, VmError};
 use std::io;
 use std::io::Read;
 
@@ -15,139 +15,214 @@ pub static RC: Field = Field {width: 3, lsb: 0};
 pub static RL: Field = Field {width: 3, lsb: 25};
 pub static VL: Field = Field {width: 25, lsb: 0};
 
-// extracts the value of a field from an instruction word. It does this by shifting the instruction 
-// word to the right by the number of least significant bits specified by the lsb field of the Field struct, 
+// extracts the value of a field from an instruction word. It does this by shifting the instruction
+// word to the right by the number of least significant bits specified by the lsb field of the Field struct,
 // and then masking the result
 pub fn get(field: &Field, instruction: u32) -> usize {
 	((instruction >> field.lsb) & mask(field.width)).try_into().unwrap()
 }
 
-// generates a mask for a given number of bits by shifting the value 1 to 
+// generates a mask for a given number of bits by shifting the value 1 to
 // the left by the given number of bits and then subtracting 1.
 fn mask(bits: u32) -> u32 {
 	(1 << bits) - 1
 }
 
-// Conditional Load Operator
-pub fn cond_move(vm: &mut Vm, word: u32) {
-	// Conditional Load
-	let a = get(&RA, word);
-	let b = get(&RB, word);
-	let c = get(&RC, word);
+// The dispatch-friendly form `Vm.prog` is built from. Unlike `disasm::Instruction`
+// (which exists for readable disassembly and is fine to spend a `usize` per field
+// on), this is the hot-loop representation: registers are always 3-bit indices
+// (0..8) and fit comfortably in a `u8`, so packing them keeps a predecoded program
+// several times smaller and more cache-friendly than reusing `disasm::Instruction`.
+#[derive(Debug, Clone, Copy)]
+pub struct DecodedInst {
+	pub opcode: u8,
+	pub a: u8,
+	pub b: u8,
+	pub c: u8,
+	pub val: u32,
+}
+
+// Decodes a word into the packed hot-loop form. Mirrors `disasm::decode` field for
+// field, but never allocates and stores indices as `u8`. Deliberately infallible:
+// an out-of-range opcode is only ever a fault if the program actually reaches it,
+// so this just carries the raw opcode through unchecked and lets `Vm::execute`'s
+// dispatch `match` report `InvalidOpcode` at the moment it would have been run —
+// matching `run`'s long-standing lazy-fault contract instead of rejecting whole
+// segments (including unreachable data words) up front.
+pub fn decode_packed(word: u32) -> DecodedInst {
+	let opcode = ((word >> 28) & (1 << 4) - 1) as u8;
+	if opcode == 13 {
+		DecodedInst {
+			opcode,
+			a: get(&RL, word) as u8,
+			b: 0,
+			c: 0,
+			val: get(&VL, word) as u32,
+		}
+	} else {
+		DecodedInst {
+			opcode,
+			a: get(&RA, word) as u8,
+			b: get(&RB, word) as u8,
+			c: get(&RC, word) as u8,
+			val: 0,
+		}
+	}
+}
+
+// Decodes every word in `words` into the packed dispatch vector `Vm.prog` drives its
+// hot loop over. Never fails, for the same reason `decode_packed` doesn't.
+pub fn predecode_packed(words: &[u32]) -> Vec<DecodedInst> {
+	words.iter().map(|&word| decode_packed(word)).collect()
+}
+
+// Looks up the segment `seg` register holds, failing with UnmappedSegment if the
+// liveness bitset doesn't have it set, so seg_load/seg_store/load_prog can't read
+// a stale or never-allocated segment id even though `memory` still has a (possibly
+// zero-length) `Vec` sitting at that index.
+pub(crate) fn segment<'a>(vm: &'a Vm, seg: u32) -> Result<&'a Vec<u32>, VmError> {
+	if !vm.mapped.get(seg as usize) {
+		return Err(VmError::UnmappedSegment(seg));
+	}
+	vm.memory.get(seg as usize).ok_or(VmError::UnmappedSegment(seg))
+}
+
+pub(crate) fn segment_mut<'a>(vm: &'a mut Vm, seg: u32) -> Result<&'a mut Vec<u32>, VmError> {
+	if !vm.mapped.get(seg as usize) {
+		return Err(VmError::UnmappedSegment(seg));
+	}
+	let len = vm.memory.len();
+	if (seg as usize) < len {
+		Ok(&mut vm.memory[seg as usize])
+	} else {
+		Err(VmError::UnmappedSegment(seg))
+	}
+}
+
+// Every handler below now takes its A/B/C fields already extracted, rather than the
+// raw word, so `run`'s hot loop only pays the `get()` bit-twiddling cost once per
+// instruction (at predecode time) instead of once per execution.
 
+// Conditional Load Operator
+pub fn cond_move(vm: &mut Vm, a: usize, b: usize, c: usize) -> Result<(), VmError> {
 	match vm.registers[c] {
 		0 => {} // Do nothing if c is 0
 		_ => vm.registers[a] = vm.registers[b], // Otherwise, set a to b
 	}
+	Ok(())
 }
 
 // Segmented Load Operator
 // This function is using indexing to access the value in the memory array at the indices specified by the b and c registers.
-pub fn seg_load(vm: &mut Vm, word: u32) {
-	// Segmented Load
-	let a = get(&RA, word);
-	let b = get(&RB, word);
-	let c = get(&RC, word);
-	vm.registers[a] = vm.memory[vm.registers[b] as usize][vm.registers[c] as usize];
+pub fn seg_load(vm: &mut Vm, a: usize, b: usize, c: usize) -> Result<(), VmError> {
+	let seg = vm.registers[b];
+	let offset = vm.registers[c];
+	let value = *segment(vm, seg)?
+		.get(offset as usize)
+		.ok_or(VmError::SegmentFault { seg, offset })?;
+	vm.registers[a] = value;
+	Ok(())
 }
 
 // Segmented Store Operator
-pub fn seg_store(vm: &mut Vm, word: u32) {
-	let a = get(&RA, word);
-	let b = get(&RB, word);
-	let c = get(&RC, word);
-
-	 // Use indexing to set the value in the memory array at the indices specified by the a and b registers.
-	vm.memory[vm.registers[a] as usize][vm.registers[b] as usize] = vm.registers[c];
-}
-
-// Add Operator  
-//This function adds the values stored in the bth and cth elements of the vm object's 
+pub fn seg_store(vm: &mut Vm, a: usize, b: usize, c: usize) -> Result<(), VmError> {
+	let seg = vm.registers[a];
+	let offset = vm.registers[b];
+	let value = vm.registers[c];
+	let slot = segment_mut(vm, seg)?
+		.get_mut(offset as usize)
+		.ok_or(VmError::SegmentFault { seg, offset })?;
+	*slot = value;
+	Ok(())
+}
+
+// Add Operator
+//This function adds the values stored in the bth and cth elements of the vm object's
 //registers array and stores the result in the ath element of the array.
-pub fn add(vm: &mut Vm, word: u32) {
-	let a = get(&RA, word);
-	let b = get(&RB, word);
-	let c = get(&RC, word);
+pub fn add(vm: &mut Vm, a: usize, b: usize, c: usize) -> Result<(), VmError> {
 	vm.registers[a] = ((vm.registers[b] as u64 + vm.registers[c] as u64) % (1_u64 << 32)).try_into().unwrap();
+	Ok(())
 }
 
 // Multiply Operator
-//This function multiplys the values stored in the bth and cth elements of the vm object's 
+//This function multiplys the values stored in the bth and cth elements of the vm object's
 //registers array and stores the result in the ath element of the array.
-pub fn mul(vm: &mut Vm, word: u32) {
-	let a = get(&RA, word);
-	let b = get(&RB, word);
-	let c = get(&RC, word);
+pub fn mul(vm: &mut Vm, a: usize, b: usize, c: usize) -> Result<(), VmError> {
 	vm.registers[a] = ((vm.registers[b] as u64 * vm.registers[c] as u64) % (1_u64 << 32)).try_into().unwrap();
+	Ok(())
 }
 
 // Divide Operator
-// this code performs a division operation on the values 
+// this code performs a division operation on the values
 // stored in two of the virtual machine's registers, storing the result in a third register.
-pub fn div(vm: &mut Vm, word: u32) {
-	let a = get(&RA, word);
-	let b = get(&RB, word);
-	let c = get(&RC, word);
-	vm.registers[a] = vm.registers[b] / vm.registers[c];	
+pub fn div(vm: &mut Vm, a: usize, b: usize, c: usize) -> Result<(), VmError> {
+	if vm.registers[c] == 0 {
+		return Err(VmError::DivByZero);
+	}
+	vm.registers[a] = vm.registers[b] / vm.registers[c];
+	Ok(())
 }
 
 // Bitwise NAND Operator
-// Takes a vm object and an integer as arguments, extracts the values
-// of three registers from the integer, performs a bitwise AND 
-// operation on two of the register values, negates the result, and stores it in the third register.
-pub fn nand(vm: &mut Vm, word: u32) {
-	let a = get(&RA, word);
-	let b = get(&RB, word);
-	let c = get(&RC, word);
-	vm.registers[a] = !(vm.registers[b] & vm.registers[c]);	
+// Takes a vm object and the already-extracted A/B/C register indices, performs a
+// bitwise AND operation on two of the register values, negates the result, and stores it in the third register.
+pub fn nand(vm: &mut Vm, a: usize, b: usize, c: usize) -> Result<(), VmError> {
+	vm.registers[a] = !(vm.registers[b] & vm.registers[c]);
+	Ok(())
 }
 
 // Halt Operator
-pub fn halt(_vm: &mut Vm) {
-	std::process::exit(0);
+// Rather than ending the process outright, this reports a clean Halted condition so
+// an embedding caller (run, or tests) decides how to react to the program finishing.
+pub fn halt(_vm: &mut Vm) -> Result<(), VmError> {
+	Err(VmError::Halted)
 }
 
 // Map Segment Operator
-// Function for managing the allocation of memory segments in a 
+// Function for managing the allocation of memory segments in a
 // virtual machine. It allows for the creation of new segments or the re-use of previously unmapped segments
-pub fn map_seg(vm: &mut Vm, word: u32) {
-	let b = get(&RB, word);
-	let c = get(&RC, word);
+pub fn map_seg(vm: &mut Vm, b: usize, c: usize) -> Result<(), VmError> {
+	let segment_number;
 	if vm.unmapped_segs.len() != 0 {
-		let segment_number = vm.unmapped_segs.pop().unwrap();
+		segment_number = vm.unmapped_segs.pop().unwrap();
 		vm.memory[segment_number] = vec![0; vm.registers[c] as usize];
-		vm.registers[b] = segment_number as u32;
-	} 
+	}
 	else {
 		vm.max_mapped_seg += 1;
+		segment_number = vm.max_mapped_seg;
 		vm.memory.push(vec![0; vm.registers[c] as usize]);
-		vm.registers[b] = vm.max_mapped_seg as u32;
 	}
+	vm.mapped.set(segment_number);
+	vm.registers[b] = segment_number as u32;
+	Ok(())
 }
 
 // Unmap Segment Operator
-// Used for managing the memory segments in a 
-// virtual machine for unmapping or removing a memory segment fromm memory
-pub fn unmap_seg(vm: &mut Vm, word: u32) {
-	let c = get(&RC, word);
-
-	vm.memory[vm.registers[c] as usize].clear();
-	vm.unmapped_segs.push(vm.registers[c].try_into().unwrap());
+// Used for managing the memory segments in a virtual machine for unmapping or
+// removing a memory segment from memory. Errors on a segment that's already
+// unmapped (or was never mapped), rather than silently double-freeing its id.
+pub fn unmap_seg(vm: &mut Vm, c: usize) -> Result<(), VmError> {
+	let seg = vm.registers[c];
+	if !vm.mapped.get(seg as usize) {
+		return Err(VmError::UnmappedSegment(seg));
+	}
+	vm.mapped.clear(seg as usize);
+	vm.memory[seg as usize].clear();
+	vm.unmapped_segs.push(seg as usize);
+	Ok(())
 }
 
 // Output Operator
-//Uses the word to extract a single register value 
-//from the vm's registers array and prints 
-//the char representation of the register
-pub fn output(vm: &mut Vm, word: u32) {
-	let c = get(&RC, word);
+//Uses the already-extracted register index to print the char representation
+//of that register's value
+pub fn output(vm: &mut Vm, c: usize) -> Result<(), VmError> {
 	print!("{}", vm.registers[c] as u8 as char);
+	Ok(())
 }
 
 //Input Operator
 //The function assigns the value of value to the c register of the vm struct
-pub fn input(vm: &mut Vm, word: u32) {
-	let c = get(&RC, word);
+pub fn input(vm: &mut Vm, c: usize) -> Result<(), VmError> {
     let mut buffer: [u8; 1] = [0; 1];
 	let num = io::stdin().read(&mut buffer);
     let value = match num {
@@ -155,23 +230,11 @@ pub fn input(vm: &mut Vm, word: u32) {
         Err(_) => !0_u32
     };
 	vm.registers[c] = value;
-}
-
-// Load Program Operator
-pub fn load_prog(vm: &mut Vm, word: u32) {
-	let b = get(&RB, word);
-	let c = get(&RC, word);
-
-
-	if vm.registers[b] != 0 {
-		vm.memory[0] = vm.memory[vm.registers[b] as usize].clone();
-    }
-	vm.prog_count = vm.registers[c];
+	Ok(())
 }
 
 // Load Value
-pub fn load_val(vm: &mut Vm, word: u32) {
-	let value = get(&VL, word);
-	let a = get(&RL, word);
-	vm.registers[a as usize] = value as u32;
-}
\ No newline at end of file
+pub fn load_val(vm: &mut Vm, a: usize, val: u32) -> Result<(), VmError> {
+	vm.registers[a] = val;
+	Ok(())
+}
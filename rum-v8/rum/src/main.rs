@@ -0,0 +1,83 @@
+mod asm;
+mod bitset;
+mod disasm;
+mod instructs;
+mod rum;
+mod trace;
+
+use rum::Vm;
+use std::env;
+use std::error::Error;
+use std::fs;
+
+fn main() {
+	let args: Vec<String> = env::args().collect();
+
+	let result = match args.get(1).map(String::as_str) {
+		Some("disasm") => run_disasm(args.get(2)),
+		Some("asm") => run_asm(args.get(2), args.get(3)),
+		Some("trace") => run_trace(args.get(2)),
+		_ => run_vm(),
+	};
+
+	if let Err(e) = result {
+		eprintln!("rum: {}", e);
+		std::process::exit(1);
+	}
+}
+
+// Default behavior: boot a program (from argv[1], or stdin with no argument) and
+// run it to completion.
+fn run_vm() -> Result<(), Box<dyn Error>> {
+	let mut vm = Vm::new_vm();
+	vm.boot()?;
+	vm.run()?;
+	Ok(())
+}
+
+fn read_words(path: &str) -> std::io::Result<Vec<u32>> {
+	Ok(fs::read(path)?
+		.chunks_exact(4)
+		.map(|w| u32::from_be_bytes(w.try_into().unwrap()))
+		.collect())
+}
+
+// `rum disasm <program>`: the `rumdump`-style listing the disasm module exists for.
+fn run_disasm(path: Option<&String>) -> Result<(), Box<dyn Error>> {
+	let path = path.ok_or("usage: rum disasm <program>")?;
+	let words = read_words(path)?;
+	for (offset, inst) in disasm::disassemble(&words) {
+		match inst {
+			Ok(inst) => println!("{:>6}  {}", offset, inst),
+			Err(e) => println!("{:>6}  ; {}", offset, e),
+		}
+	}
+	Ok(())
+}
+
+// `rum asm <source> <out>`: assembles mnemonic source into a UM binary, so a
+// program can round-trip through `rum asm` -> `rum`/`rum disasm`.
+fn run_asm(src_path: Option<&String>, out_path: Option<&String>) -> Result<(), Box<dyn Error>> {
+	let src_path = src_path.ok_or("usage: rum asm <source> <out>")?;
+	let out_path = out_path.ok_or("usage: rum asm <source> <out>")?;
+	let src = fs::read_to_string(src_path)?;
+	let program = asm::assemble(&src)?;
+	let mut out = fs::File::create(out_path)?;
+	asm::write_program(&program, &mut out)?;
+	Ok(())
+}
+
+// `rum trace <program>`: runs a program to completion with tracing on and prints
+// the columnar trace, for step-through debugging or diffing two runs.
+fn run_trace(path: Option<&String>) -> Result<(), Box<dyn Error>> {
+	let path = path.ok_or("usage: rum trace <program>")?;
+	let words = read_words(path)?;
+	let mut vm = Vm::new_vm();
+	vm.load_segment0(words);
+	let (cause, trace) = vm.run_traced();
+	print!("{}", trace);
+	match cause {
+		trace::ExitCause::Halted => Ok(()),
+		trace::ExitCause::Faulted(e) => Err(Box::new(e)),
+	}
+}
@@ -1,102 +1,310 @@
+use crate::bitset::SegBitset;
 use crate::instructs::*;
+use crate::trace::{ExitCause, Trace, TraceEntry};
 use std::env;
+use std::fmt;
 use std::io::Read;
 
 // This public structure of the virtual machine will contain the registers and memory of the segments
 // of the opcode instructions and will contain the counter for the program when machine is running
 pub struct Vm {
-	pub registers: Vec<u32>,
+	pub registers: [u32; 8],
 	pub memory: Vec<Vec<u32>>,
 	pub unmapped_segs: Vec<usize>,
 	pub max_mapped_seg: usize,
 	pub prog_count: u32,
+	// Tracks which segment ids are currently live, independent of whether `memory`
+	// happens to hold a zero-length `Vec` at that index. `map_seg`/`unmap_seg` keep
+	// this in sync so a stale or never-allocated id is rejected in O(1).
+	pub(crate) mapped: SegBitset,
+	// Segment 0, predecoded once at boot (again on any `load_prog` that installs
+	// a new segment 0, and word by word on stores into segment 0) so the hot loop in `run` drives dispatch off already-extracted
+	// register indices instead of re-running `get()` on every executed word.
+	prog: Vec<DecodedInst>,
+	// Accumulates entries while `step`/`run_traced` are in use. `run` never touches
+	// this, so plain execution pays nothing for it.
+	pub trace: Trace,
 }
 
-// Virtual machine that will start to boot and set memory and increment counter 
+// Every way the Vm can fail to make progress, instead of panicking or killing the process.
+// This lets a caller embed the Vm and decide for itself how to report a fault.
+#[derive(Debug)]
+pub enum VmError {
+	InvalidOpcode(u32),
+	DivByZero,
+	SegmentFault { seg: u32, offset: u32 },
+	UnmappedSegment(u32),
+	PcOutOfBounds,
+	Io(std::io::Error),
+	Halted,
+}
+
+impl fmt::Display for VmError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			VmError::InvalidOpcode(op) => write!(f, "invalid opcode: {}", op),
+			VmError::DivByZero => write!(f, "division by zero"),
+			VmError::SegmentFault { seg, offset } => {
+				write!(f, "segment fault: seg {} offset {} out of bounds", seg, offset)
+			}
+			VmError::UnmappedSegment(seg) => write!(f, "segment {} is not mapped", seg),
+			VmError::PcOutOfBounds => write!(f, "program counter ran past the end of segment 0"),
+			VmError::Io(e) => write!(f, "io error: {}", e),
+			VmError::Halted => write!(f, "halted"),
+		}
+	}
+}
+
+impl std::error::Error for VmError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			VmError::Io(e) => Some(e),
+			_ => None,
+		}
+	}
+}
+
+impl From<std::io::Error> for VmError {
+	fn from(e: std::io::Error) -> Self {
+		VmError::Io(e)
+	}
+}
+
+// Virtual machine that will start to boot and set memory and increment counter
 impl Vm {
 
-	// constructs and initializes a new instance of the Vm struct. 
+	// constructs and initializes a new instance of the Vm struct.
 	// this allows to create new instances of the struct
 	pub fn new_vm() -> Self {
 		Vm {
-			registers: vec![0; 8],
+			registers: [0; 8],
             memory: Vec::new(),
             unmapped_segs: Vec::new(),
             max_mapped_seg: 0,
             prog_count: 0,
+            mapped: SegBitset::new(),
+            prog: Vec::new(),
+            trace: Trace::new(),
 		}
 	}
 	// Initliaze VM by taking argument
-	pub fn boot(&mut self) {
+	pub fn boot(&mut self) -> Result<(), VmError> {
 
 		let args: Vec<String> = env::args().collect();
 	    let input: Option<&str>;
-	    
+
 	    if args.len() == 2 {
 	    	input = Some(&args[1]);
 	    } else {
 	    	input = None;
 	    }
-	    
+
 	    	// Reused from Rumdump to read in all input data and defines a vector called instructions
 			// and sets collection of u32 values and then pushes vector into memory field of the self object
 	    	let mut raw_reader: Box<dyn std::io::BufRead> = match input {
 			None => Box::new(std::io::BufReader::new(std::io::stdin())),
 			Some(filename) => Box::new(std::io::BufReader::new(
-				std::fs::File::open(filename).unwrap(),
+				std::fs::File::open(filename)?,
 			)),
 		};
-		
+
 			let mut buf = Vec::<u8>::new();
-			raw_reader.read_to_end(&mut buf).unwrap();
-		
+			raw_reader.read_to_end(&mut buf)?;
+
 			let instructions: Vec<u32> = buf
 				.chunks_exact(4)
 				.map(|x| u32::from_be_bytes(x.try_into().unwrap()))
 				.collect();
-			self.memory.push(instructions); 
+			self.load_segment0(instructions);
+			Ok(())
 	}
 
-	// Run instruction executbales
-	pub fn run(&mut self) {
+	// Installs `instructions` as segment 0: predecodes it into `prog` and marks the
+	// segment live. Split out of `boot` so callers that already have a program's
+	// words in hand (the `rum trace` subcommand, tests) don't have to go through
+	// argv/stdin parsing to run it.
+	pub fn load_segment0(&mut self, instructions: Vec<u32>) {
+		self.prog = predecode_packed(&instructions);
+		self.memory.push(instructions);
+		self.mapped.set(0);
+	}
+
+	// Run instruction executbales. Returns Ok(()) once the program halts cleanly,
+	// or the VmError that stopped execution otherwise. Drives the loop over the
+	// predecoded `prog` vector rather than re-fetching and re-decoding a raw word
+	// on every iteration.
+	pub fn run(&mut self) -> Result<(), VmError> {
 		loop {
-			let instruction = self.get_instruct();
-			self.execute(instruction);
+			let inst = *self
+				.prog
+				.get(self.prog_count as usize)
+				.ok_or(VmError::PcOutOfBounds)?;
+			self.prog_count += 1;
+			match self.execute(inst) {
+				Ok(()) => {}
+				Err(VmError::Halted) => return Ok(()),
+				Err(e) => return Err(e),
+			}
 		}
 	}
-	// Retrieve instruction counts
-	fn get_instruct(&mut self) -> u32 {
-		let instruction = self.memory[0][self.prog_count as usize];
+
+	// Executes exactly one instruction and records it in `self.trace`, so a caller
+	// can single-step a program. Returns `Ok(Some(Halted))` the instant the program
+	// halts cleanly, `Ok(None)` if execution should continue, and propagates any
+	// other fault via `Err` (mirroring `run`).
+	pub fn step(&mut self) -> Result<Option<ExitCause>, VmError> {
+		let idx = self.prog_count as usize;
+		let word = *self
+			.memory
+			.get(0)
+			.and_then(|seg0| seg0.get(idx))
+			.ok_or(VmError::PcOutOfBounds)?;
+		let inst = *self.prog.get(idx).ok_or(VmError::PcOutOfBounds)?;
+		let before = self.registers;
 		self.prog_count += 1;
-		instruction
-	}
-
-	pub fn execute(&mut self, word: u32){
-
-		// The >> operator shifts the bits of word to the right by 28 places, effectively moving the opcode bits to the rightmost position in the 
-		// resulting value. The & operator then performs a bitwise AND operation with the value (1 << 4) - 1, 
-		// which is a mask that has the first 4 bits set to 1 and the rest set to 0. This mask is used to isolate the first 4 bits of the word, which contain the opcode.
-		// The resulting value is the opcode extracted from the word.
-		let opcode = (word >> 28) & (1 << 4) - 1;
-
-		// Excecute our Opcode conditions
-		match opcode {
-			0 =>  cond_move(self, word),
-			1 =>  seg_load(self, word),
-			2 =>  seg_store(self, word),
-			3 =>  add(self, word),
-			4 =>  mul(self, word),
-			5 =>  div(self, word),
-			6 =>  nand(self, word),
-			7 =>  halt(self),
-			8 =>  map_seg(self, word),
-			9 =>  unmap_seg(self, word),
-			10 => output(self, word),
-			11 => input(self, word),
-			12 => load_prog(self, word),
-			13 => load_val(self, word),
-			 _ => panic!("Error")
 
+		let record = |vm: &mut Self| {
+			let changed = before
+				.iter()
+				.zip(vm.registers.iter())
+				.position(|(old, new)| old != new)
+				.map(|reg| (reg, vm.registers[reg]));
+			vm.trace.push(TraceEntry { prog_count: idx as u32, word, opcode: inst.opcode as u32, changed });
 		};
+
+		match self.execute(inst) {
+			Ok(()) => {
+				record(self);
+				Ok(None)
+			}
+			Err(VmError::Halted) => {
+				record(self);
+				Ok(Some(ExitCause::Halted))
+			}
+			Err(e) => Err(e),
+		}
+	}
+
+	// Runs to completion while tracing every instruction, so the execution can be
+	// replayed or diffed afterward. Returns the exit condition and the trace
+	// accumulated since the last call, instead of killing the process or discarding
+	// what ran before a fault.
+	pub fn run_traced(&mut self) -> (ExitCause, Trace) {
+		loop {
+			match self.step() {
+				Ok(Some(cause)) => return (cause, std::mem::replace(&mut self.trace, Trace::new())),
+				Ok(None) => {}
+				Err(e) => return (ExitCause::Faulted(e), std::mem::replace(&mut self.trace, Trace::new())),
+			}
+		}
 	}
-} 
\ No newline at end of file
+
+	// Dispatches an already-decoded instruction. `load_prog` is handled here, rather
+	// than in `instructs.rs`, because re-predecoding segment 0 needs access to `prog`.
+	pub fn execute(&mut self, inst: DecodedInst) -> Result<(), VmError> {
+		let (a, b, c) = (inst.a as usize, inst.b as usize, inst.c as usize);
+		match inst.opcode {
+			0 => cond_move(self, a, b, c),
+			1 => seg_load(self, a, b, c),
+			2 => {
+				seg_store(self, a, b, c)?;
+				// A store into segment 0 rewrites code, so keep `prog` in step with it.
+				if self.registers[a] == 0 {
+					if let Some(slot) = self.prog.get_mut(self.registers[b] as usize) {
+						*slot = decode_packed(self.registers[c]);
+					}
+				}
+				Ok(())
+			}
+			3 => add(self, a, b, c),
+			4 => mul(self, a, b, c),
+			5 => div(self, a, b, c),
+			6 => nand(self, a, b, c),
+			7 => halt(self),
+			8 => map_seg(self, b, c),
+			9 => unmap_seg(self, c),
+			10 => output(self, c),
+			11 => input(self, c),
+			12 => self.load_prog(b, c),
+			13 => load_val(self, a, inst.val),
+			_ => Err(VmError::InvalidOpcode(inst.opcode as u32)),
+		}
+	}
+
+	// Load Program Operator. When register b names a segment, that segment's words
+	// become the new segment 0 and are predecoded into `prog`; when b is 0, this is
+	// just a jump, so neither the raw copy nor the redecode happens.
+	fn load_prog(&mut self, b: usize, c: usize) -> Result<(), VmError> {
+		if self.registers[b] != 0 {
+			let seg = self.registers[b];
+			let words = segment(self, seg)?.clone();
+			self.prog = predecode_packed(&words);
+			self.memory[0] = words;
+		}
+		self.prog_count = self.registers[c];
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::asm;
+
+	fn vm_running(src: &str) -> Vm {
+		let mut vm = Vm::new_vm();
+		vm.load_segment0(asm::assemble(src).unwrap());
+		vm
+	}
+
+	#[test]
+	fn runs_a_program_to_a_clean_halt() {
+		let mut vm = vm_running("loadval r0 72\nhalt\n");
+		assert!(vm.run().is_ok());
+		assert_eq!(vm.registers[0], 72);
+	}
+
+	#[test]
+	fn div_by_zero_faults_instead_of_panicking() {
+		let mut vm = vm_running("loadval r1 0\nloadval r2 5\ndiv r3 r2 r1\nhalt\n");
+		assert!(matches!(vm.run(), Err(VmError::DivByZero)));
+	}
+
+	#[test]
+	fn load_from_an_unmapped_segment_faults() {
+		let mut vm = vm_running("loadval r2 99\nload r1 r2 r3\nhalt\n");
+		assert!(matches!(vm.run(), Err(VmError::UnmappedSegment(99))));
+	}
+
+	#[test]
+	fn running_off_the_end_of_segment_0_faults() {
+		let mut vm = vm_running("loadval r0 1\n");
+		assert!(matches!(vm.run(), Err(VmError::PcOutOfBounds)));
+	}
+
+	#[test]
+	fn an_unassigned_opcode_faults_only_when_reached() {
+		// opcode 14 is never assigned to a handler; it sits as dead data after `halt`
+		// so the old lazy-fault model never reaches (and never faults on) it.
+		let mut vm = vm_running("halt\n");
+		vm.memory[0].push(14 << 28);
+		vm.prog.push(crate::instructs::decode_packed(14 << 28));
+		assert!(vm.run().is_ok());
+	}
+
+	#[test]
+	fn step_records_a_trace_entry_per_instruction() {
+		let mut vm = vm_running("loadval r0 72\noutput r0\nhalt\n");
+		let (cause, trace) = vm.run_traced();
+		assert!(matches!(cause, ExitCause::Halted));
+		assert_eq!(trace.entries.len(), 3);
+		assert_eq!(trace.entries[0].changed, Some((0, 72)));
+		assert_eq!(trace.entries[1].changed, None);
+	}
+
+	#[test]
+	fn step_before_boot_faults_instead_of_panicking() {
+		let mut vm = Vm::new_vm();
+		assert!(matches!(vm.step(), Err(VmError::PcOutOfBounds)));
+	}
+}
@@ -0,0 +1,80 @@
+use crate::disasm;
+use crate::rum::VmError;
+use std::fmt;
+
+// One executed instruction: where it ran from, what word it was, and the single
+// register (if any) that execution changed. At most one register ever changes per
+// UM instruction, so a before/after register-file diff is enough to capture it.
+#[derive(Debug, Clone, Copy)]
+pub struct TraceEntry {
+	pub prog_count: u32,
+	pub word: u32,
+	pub opcode: u32,
+	pub changed: Option<(usize, u32)>,
+}
+
+// Why execution stopped. `Faulted` carries the error a plain `run` would have
+// returned via `Err`, so `run_traced` can hand back a trace of everything that ran
+// up to the fault instead of discarding it.
+#[derive(Debug)]
+pub enum ExitCause {
+	Halted,
+	Faulted(VmError),
+}
+
+// A recording of executed instructions, in execution order (empty if the first
+// step faulted). Built up by
+// `Vm::step`/`Vm::run_traced` for deterministic replay and step-through debugging.
+#[derive(Debug, Default)]
+pub struct Trace {
+	pub entries: Vec<TraceEntry>,
+}
+
+impl Trace {
+	pub fn new() -> Self {
+		Trace { entries: Vec::new() }
+	}
+
+	pub(crate) fn push(&mut self, entry: TraceEntry) {
+		self.entries.push(entry);
+	}
+}
+
+// Columnar text: one line per entry, `offset\tmnemonic\tchanged-register`, so two
+// traces can be diffed with ordinary text tools.
+impl fmt::Display for Trace {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		for entry in &self.entries {
+			let mnemonic = match disasm::decode(entry.word) {
+				Ok(inst) => inst.to_string(),
+				Err(_) => format!("opcode {}", entry.opcode),
+			};
+			let changed = match entry.changed {
+				Some((reg, val)) => format!("r{}:={}", reg, val),
+				None => "-".to_string(),
+			};
+			writeln!(f, "{}\t{}\t{}", entry.prog_count, mnemonic, changed)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn display_renders_mnemonic_and_changed_register() {
+		let mut trace = Trace::new();
+		trace.push(TraceEntry { prog_count: 0, word: (13 << 28) | 72, opcode: 13, changed: Some((0, 72)) });
+		trace.push(TraceEntry { prog_count: 1, word: 7 << 28, opcode: 7, changed: None });
+		assert_eq!(trace.to_string(), "0\tload-val r0 := 72\tr0:=72\n1\thalt\t-\n");
+	}
+
+	#[test]
+	fn display_falls_back_to_the_raw_opcode_on_an_undecodable_word() {
+		let mut trace = Trace::new();
+		trace.push(TraceEntry { prog_count: 0, word: 14 << 28, opcode: 14, changed: None });
+		assert_eq!(trace.to_string(), "0\topcode 14\t-\n");
+	}
+}